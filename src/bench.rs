@@ -0,0 +1,136 @@
+use crate::bx;
+use crate::inference::InferenceBackend;
+use crate::nifti_io;
+use crate::progress::PlainReporter;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Per-stage wall-clock timings collected while running the pipeline once.
+///
+/// Threaded through [`bx::run_bx`] (and the NIfTI read/write around it) so
+/// `--bench` can report a breakdown without every stage needing to know
+/// it's being benchmarked.
+#[derive(Default)]
+pub struct TimingCollector(Vec<(&'static str, Duration)>);
+
+impl TimingCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time a single stage and record its duration under `name`.
+    pub fn record<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.0.push((name, start.elapsed()));
+        result
+    }
+}
+
+/// min/mean/max wall-clock time for one pipeline stage across bench iterations.
+#[derive(Serialize)]
+pub struct StageStats {
+    pub stage: &'static str,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Benchmark results for a single input file.
+#[derive(Serialize)]
+pub struct FileBenchReport {
+    pub file: PathBuf,
+    pub iterations: usize,
+    pub warmup: usize,
+    pub voxels: usize,
+    pub voxels_per_sec: f64,
+    pub stages: Vec<StageStats>,
+}
+
+/// Run the full pipeline on `input` `iterations` times, discarding the
+/// first `warmup` runs, and report min/mean/max time per stage plus
+/// voxels/sec throughput over the measured runs.
+///
+/// Outputs are written to a pair of scratch files in the temp directory,
+/// overwritten each iteration and deleted once all iterations have run —
+/// `--bench` measures the pipeline, it doesn't keep the results.
+pub fn bench_file(
+    input: &Path,
+    backend: &mut dyn InferenceBackend,
+    iterations: usize,
+    warmup: usize,
+) -> Result<FileBenchReport> {
+    let reporter = PlainReporter;
+    let scratch_dir = std::env::temp_dir();
+    let tbx_path = scratch_dir.join(format!("rustbx_bench_{}.nii.gz", std::process::id()));
+    let tbxmask_path = scratch_dir.join(format!("rustbx_bench_{}_mask.nii.gz", std::process::id()));
+
+    let mut stage_names: Vec<&'static str> = Vec::new();
+    let mut stage_durations: Vec<Vec<Duration>> = Vec::new();
+    let mut voxels = 0usize;
+    let mut measured_total = Duration::ZERO;
+    let mut measured_runs = 0usize;
+
+    for i in 0..iterations {
+        let mut timing = TimingCollector::new();
+
+        let (header, data) = timing.record("nifti_read", || nifti_io::read_nifti(input))?;
+        voxels = data.len();
+
+        let (mask, brain) = bx::run_bx(&data, backend, &reporter, &mut timing)?;
+
+        timing.record("nifti_write", || -> Result<()> {
+            nifti_io::write_nifti(&tbx_path, &header, &brain)?;
+            nifti_io::write_nifti_u8(&tbxmask_path, &header, &mask)?;
+            Ok(())
+        })?;
+
+        if i >= warmup {
+            measured_total += timing.0.iter().map(|(_, d)| *d).sum();
+            measured_runs += 1;
+            for (idx, (stage, duration)) in timing.0.into_iter().enumerate() {
+                if idx == stage_names.len() {
+                    stage_names.push(stage);
+                    stage_durations.push(Vec::new());
+                }
+                stage_durations[idx].push(duration);
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&tbx_path);
+    let _ = std::fs::remove_file(&tbxmask_path);
+
+    let stages = stage_names
+        .into_iter()
+        .zip(stage_durations)
+        .map(|(stage, durations)| {
+            let min = durations.iter().min().copied().unwrap_or_default();
+            let max = durations.iter().max().copied().unwrap_or_default();
+            let mean = durations.iter().sum::<Duration>() / durations.len() as u32;
+            StageStats {
+                stage,
+                min_ms: min.as_secs_f64() * 1000.0,
+                mean_ms: mean.as_secs_f64() * 1000.0,
+                max_ms: max.as_secs_f64() * 1000.0,
+            }
+        })
+        .collect();
+
+    let voxels_per_sec = if measured_total.as_secs_f64() > 0.0 {
+        (voxels * measured_runs) as f64 / measured_total.as_secs_f64()
+    } else {
+        0.0
+    };
+
+    Ok(FileBenchReport {
+        file: input.to_path_buf(),
+        iterations,
+        warmup,
+        voxels,
+        voxels_per_sec,
+        stages,
+    })
+}
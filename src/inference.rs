@@ -1,53 +1,253 @@
-use anyhow::{Context, Result};
-use ndarray::{ArrayD, IxDyn};
-use ort::session::Session;
-use ort::value::Tensor;
+use anyhow::Result;
+use ndarray::ArrayD;
 
-/// Create an ONNX inference session from model bytes.
+/// A loadable, runnable inference engine.
 ///
-/// This should be called once and the session reused for all files.
-pub fn create_session(model_bytes: &[u8]) -> Result<Session> {
-    let session = Session::builder()
-        .context("Failed to create session builder")?
-        .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)
-        .context("Failed to set optimization level")?
-        .commit_from_memory(model_bytes)
-        .context("Failed to load ONNX model from memory")?;
-
-    Ok(session)
+/// Abstracts over the concrete ONNX execution engine (ONNX Runtime via
+/// `ort`, or a pure-Rust engine via `tract`) so the rest of the pipeline
+/// doesn't need to care which one is in use. Enable the engine(s) you want
+/// with the `ort-backend` / `tract-backend` Cargo features.
+pub trait InferenceBackend: Send {
+    /// Load a model from raw ONNX bytes.
+    fn load(model_bytes: &[u8]) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Run inference on a 3D (H, W, D) input, returning raw logits with
+    /// whatever shape the model produces.
+    fn run(&mut self, input: &ArrayD<f32>) -> Result<ArrayD<f32>>;
 }
 
-/// Run inference on a pre-built session.
-///
-/// Input: 3D f32 array (H, W, D) — will be reshaped to (1, 1, H, W, D).
-/// Output: raw logits array with shape matching the ONNX model output.
-pub fn run_onnx(session: &mut Session, input: &ArrayD<f32>) -> Result<ArrayD<f32>> {
-    // Reshape input from (H, W, D) to (1, 1, H, W, D)
-    let shape = input.shape();
-    let shape_5d: Vec<usize> = vec![1, 1, shape[0], shape[1], shape[2]];
-    let flat_data: Vec<f32> = input.iter().cloned().collect();
-
-    // Create ort Tensor from (shape, data) tuple
-    let input_tensor = Tensor::from_array((shape_5d.as_slice(), flat_data))
-        .context("Failed to create input tensor")?;
-
-    // Get input name
-    let input_name = session.inputs()[0].name().to_string();
-
-    // Run inference
-    let outputs = session
-        .run(ort::inputs![input_name.as_str() => input_tensor])
-        .context("ONNX inference failed")?;
-
-    // Extract output tensor — returns (&Shape, &[f32])
-    let (out_shape, out_data) = outputs[0]
-        .try_extract_tensor::<f32>()
-        .context("Failed to extract output tensor")?;
-
-    // Reconstruct ArrayD from shape and data
-    let shape_vec: Vec<usize> = out_shape.iter().map(|&s| s as usize).collect();
-    let output = ArrayD::from_shape_vec(IxDyn(&shape_vec), out_data.to_vec())
-        .context("Failed to reconstruct output array")?;
-
-    Ok(output)
+#[cfg(feature = "ort-backend")]
+pub use ort_backend::{ExecutionProvider, OrtBackend};
+
+#[cfg(feature = "tract-backend")]
+pub use tract_backend::TractBackend;
+
+#[cfg(feature = "ort-backend")]
+mod ort_backend {
+    use super::InferenceBackend;
+    use anyhow::{Context, Result};
+    use half::f16;
+    use ndarray::{ArrayD, IxDyn};
+    use ort::execution_providers::{
+        CUDAExecutionProvider, CoreMLExecutionProvider, DirectMLExecutionProvider,
+        TensorRTExecutionProvider,
+    };
+    use ort::session::builder::SessionBuilder;
+    use ort::session::Session;
+    use ort::value::Tensor;
+
+    /// Execution provider to run the ONNX Runtime session on.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub enum ExecutionProvider {
+        #[default]
+        Cpu,
+        Cuda,
+        TensorRt,
+        CoreMl,
+        DirectMl,
+    }
+
+    /// Inference backend running on ONNX Runtime via the `ort` crate.
+    ///
+    /// This is the original backend: fast and GPU-capable, but it requires
+    /// the native ONNX Runtime shared library to be present at build and
+    /// run time.
+    pub struct OrtBackend {
+        session: Session,
+        fp16: bool,
+    }
+
+    impl OrtBackend {
+        /// Create an ONNX Runtime session on the given execution provider,
+        /// optionally running inference in FP16.
+        ///
+        /// If the requested execution provider fails to register (missing
+        /// driver, unsupported hardware, ...), this logs a warning and
+        /// falls back to a plain CPU session rather than erroring out.
+        pub fn load_with_options(
+            model_bytes: &[u8],
+            ep: ExecutionProvider,
+            fp16: bool,
+        ) -> Result<Self> {
+            let builder = Self::session_builder(ep)?;
+            let session = builder
+                .commit_from_memory(model_bytes)
+                .context("Failed to load ONNX model from memory")?;
+
+            Ok(Self { session, fp16 })
+        }
+
+        /// Build a session builder on `ep`, falling back to CPU (with a
+        /// warning) if registering `ep` fails instead of propagating the error.
+        fn session_builder(ep: ExecutionProvider) -> Result<SessionBuilder> {
+            let builder = Session::builder()
+                .context("Failed to create session builder")?
+                .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)
+                .context("Failed to set optimization level")?;
+
+            let registered = match ep {
+                ExecutionProvider::Cpu => return Ok(builder),
+                ExecutionProvider::Cuda => {
+                    builder.with_execution_providers([CUDAExecutionProvider::default().build()])
+                }
+                ExecutionProvider::TensorRt => builder
+                    .with_execution_providers([TensorRTExecutionProvider::default().build()]),
+                ExecutionProvider::CoreMl => {
+                    builder.with_execution_providers([CoreMLExecutionProvider::default().build()])
+                }
+                ExecutionProvider::DirectMl => builder
+                    .with_execution_providers([DirectMLExecutionProvider::default().build()]),
+            };
+
+            match registered {
+                Ok(builder) => Ok(builder),
+                Err(e) => {
+                    eprintln!(
+                        "Warning: failed to register {ep:?} execution provider ({e:#}), falling back to CPU"
+                    );
+                    Self::session_builder(ExecutionProvider::Cpu)
+                }
+            }
+        }
+    }
+
+    impl InferenceBackend for OrtBackend {
+        /// Create a CPU, FP32 ONNX Runtime session from model bytes.
+        fn load(model_bytes: &[u8]) -> Result<Self> {
+            Self::load_with_options(model_bytes, ExecutionProvider::Cpu, false)
+        }
+
+        /// Run inference on a pre-built session.
+        ///
+        /// Input: 3D f32 array (H, W, D) — will be reshaped to (1, 1, H, W, D).
+        /// In FP16 mode the input is cast to half precision before being
+        /// handed to `ort`, and the output is cast back to f32 so the
+        /// `InferenceBackend` contract stays the same regardless of mode.
+        fn run(&mut self, input: &ArrayD<f32>) -> Result<ArrayD<f32>> {
+            // Reshape input from (H, W, D) to (1, 1, H, W, D)
+            let shape = input.shape();
+            let shape_5d: Vec<usize> = vec![1, 1, shape[0], shape[1], shape[2]];
+
+            // Get input name
+            let input_name = self.session.inputs()[0].name().to_string();
+
+            let outputs = if self.fp16 {
+                let flat_data: Vec<f16> = input.iter().map(|&v| f16::from_f32(v)).collect();
+                let input_tensor = Tensor::from_array((shape_5d.as_slice(), flat_data))
+                    .context("Failed to create FP16 input tensor")?;
+                self.session
+                    .run(ort::inputs![input_name.as_str() => input_tensor])
+                    .context("ONNX inference failed")?
+            } else {
+                let flat_data: Vec<f32> = input.iter().cloned().collect();
+                let input_tensor = Tensor::from_array((shape_5d.as_slice(), flat_data))
+                    .context("Failed to create input tensor")?;
+                self.session
+                    .run(ort::inputs![input_name.as_str() => input_tensor])
+                    .context("ONNX inference failed")?
+            };
+
+            if self.fp16 {
+                let (out_shape, out_data) = outputs[0]
+                    .try_extract_tensor::<f16>()
+                    .context("Failed to extract FP16 output tensor")?;
+                let shape_vec: Vec<usize> = out_shape.iter().map(|&s| s as usize).collect();
+                let data: Vec<f32> = out_data.iter().map(|v| v.to_f32()).collect();
+                ArrayD::from_shape_vec(IxDyn(&shape_vec), data)
+                    .context("Failed to reconstruct output array")
+            } else {
+                let (out_shape, out_data) = outputs[0]
+                    .try_extract_tensor::<f32>()
+                    .context("Failed to extract output tensor")?;
+                let shape_vec: Vec<usize> = out_shape.iter().map(|&s| s as usize).collect();
+                ArrayD::from_shape_vec(IxDyn(&shape_vec), out_data.to_vec())
+                    .context("Failed to reconstruct output array")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tract-backend")]
+mod tract_backend {
+    use super::InferenceBackend;
+    use anyhow::{Context, Result};
+    use ndarray::{ArrayD, IxDyn};
+    use std::io::Cursor;
+    use tract_onnx::prelude::*;
+
+    /// Pure-Rust inference backend running on `tract`.
+    ///
+    /// Loads the same `.onnx` bytes as [`super::OrtBackend`] but runs them
+    /// through tract's typed model pipeline, with no external ONNX Runtime
+    /// dependency. This is the backend to pick when you need a fully
+    /// static binary.
+    pub struct TractBackend {
+        model: TypedRunnableModel<TypedModel>,
+    }
+
+    impl InferenceBackend for TractBackend {
+        fn load(model_bytes: &[u8]) -> Result<Self> {
+            let mut raw_model = tract_onnx::onnx()
+                .model_for_read(&mut Cursor::new(model_bytes))
+                .context("Failed to parse ONNX model with tract")?;
+
+            // Each NIfTI volume has its own (H, W, D), so the graph's
+            // declared input shape may be symbolic/dynamic rather than a
+            // fixed size. If so, pin it to named symbols (not concrete
+            // numbers) before optimizing — otherwise `into_optimized` can
+            // fail to resolve convolution/padding shapes at load time.
+            let declared = raw_model
+                .input_fact(0)
+                .context("Failed to read tract model input fact")?
+                .clone();
+            if declared.shape.as_concrete().is_none() {
+                let h = raw_model.symbols.new_with_prefix("H");
+                let w = raw_model.symbols.new_with_prefix("W");
+                let d = raw_model.symbols.new_with_prefix("D");
+                raw_model = raw_model
+                    .with_input_fact(
+                        0,
+                        InferenceFact::dt_shape(
+                            f32::datum_type(),
+                            tvec![1.into(), 1.into(), h.into(), w.into(), d.into()],
+                        ),
+                    )
+                    .context("Failed to pin tract model input shape")?;
+            }
+
+            let model = raw_model
+                .into_optimized()
+                .context("Failed to optimize tract model")?
+                .into_runnable()
+                .context("Failed to make tract model runnable")?;
+
+            Ok(Self { model })
+        }
+
+        fn run(&mut self, input: &ArrayD<f32>) -> Result<ArrayD<f32>> {
+            // Reshape input from (H, W, D) to (1, 1, H, W, D)
+            let shape = input.shape();
+            let shape_5d = [1, 1, shape[0], shape[1], shape[2]];
+            let input_5d = input
+                .clone()
+                .into_shape_with_order(IxDyn(&shape_5d))
+                .context("Failed to reshape input to 5D")?;
+
+            let input_tensor: Tensor = input_5d.into_tensor();
+            let outputs = self
+                .model
+                .run(tvec!(input_tensor.into()))
+                .context("tract inference failed")?;
+
+            let output = outputs[0]
+                .to_array_view::<f32>()
+                .context("Failed to read tract output tensor")?
+                .to_owned()
+                .into_dyn();
+
+            Ok(output)
+        }
+    }
 }
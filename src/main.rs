@@ -1,11 +1,55 @@
+mod bench;
 mod bx;
 mod inference;
+mod model_source;
 mod nifti_io;
 mod postprocess;
+mod progress;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::cell::RefCell;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use inference::InferenceBackend;
+use progress::{BarReporter, PlainReporter, StageReporter};
+
+/// Which inference engine to run the model on.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum Backend {
+    /// ONNX Runtime via the `ort` crate (requires the native ORT library).
+    #[default]
+    Ort,
+    /// Pure-Rust inference via the `tract` crate (no external dependency).
+    Tract,
+}
+
+/// Which device/execution provider to run the `ort` backend on.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum Device {
+    #[default]
+    Cpu,
+    Cuda,
+    Tensorrt,
+    Coreml,
+    Directml,
+}
+
+#[cfg(feature = "ort-backend")]
+impl From<Device> for inference::ExecutionProvider {
+    fn from(device: Device) -> Self {
+        match device {
+            Device::Cpu => inference::ExecutionProvider::Cpu,
+            Device::Cuda => inference::ExecutionProvider::Cuda,
+            Device::Tensorrt => inference::ExecutionProvider::TensorRt,
+            Device::Coreml => inference::ExecutionProvider::CoreMl,
+            Device::Directml => inference::ExecutionProvider::DirectMl,
+        }
+    }
+}
 
 /// Embedded bx model (compiled into the binary)
 static EMBEDDED_MODEL: &[u8] = include_bytes!("../models/mprage_bet_v005_mixsynthv4.onnx");
@@ -26,9 +70,83 @@ struct Args {
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Path to ONNX model file (default: embedded model)
+    /// Path to an ONNX model file, or an http(s) URL to download and cache
+    /// (default: embedded model)
     #[arg(short, long)]
-    model: Option<PathBuf>,
+    model: Option<String>,
+
+    /// Expected SHA-256 of the model, checked when `--model` is a URL
+    #[arg(long)]
+    model_sha256: Option<String>,
+
+    /// Inference backend to run the model on
+    #[arg(long, value_enum, default_value_t = Backend::Ort)]
+    backend: Backend,
+
+    /// Device/execution provider to run the `ort` backend on
+    #[arg(long, value_enum, default_value_t = Device::Cpu)]
+    device: Device,
+
+    /// Run inference in half precision (FP16) instead of FP32 (`ort` backend only)
+    #[arg(long)]
+    fp16: bool,
+
+    /// Number of files to process in parallel (builds one inference backend per worker)
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Benchmark mode: run each input N times (default 5) and report
+    /// per-stage timing instead of processing normally
+    #[arg(long, num_args = 0..=1, default_missing_value = "5")]
+    bench: Option<usize>,
+
+    /// Warm-up iterations to discard in `--bench` mode
+    #[arg(long, default_value_t = 1)]
+    bench_warmup: usize,
+
+    /// Emit the `--bench` report as JSON instead of a table
+    #[arg(long)]
+    bench_json: bool,
+}
+
+/// Load the selected inference backend, boxed behind the `InferenceBackend` trait.
+fn load_backend(
+    backend: Backend,
+    model_bytes: &[u8],
+    device: Device,
+    fp16: bool,
+) -> Result<Box<dyn InferenceBackend>> {
+    match backend {
+        Backend::Ort => {
+            #[cfg(feature = "ort-backend")]
+            {
+                Ok(Box::new(inference::OrtBackend::load_with_options(
+                    model_bytes,
+                    device.into(),
+                    fp16,
+                )?))
+            }
+            #[cfg(not(feature = "ort-backend"))]
+            {
+                bail!("rustbx was built without the `ort-backend` feature; rebuild with --features ort-backend");
+            }
+        }
+        Backend::Tract => {
+            if !matches!(device, Device::Cpu) || fp16 {
+                eprintln!(
+                    "Warning: --device and --fp16 are only supported by the `ort` backend; ignoring for tract"
+                );
+            }
+            #[cfg(feature = "tract-backend")]
+            {
+                Ok(Box::new(inference::TractBackend::load(model_bytes)?))
+            }
+            #[cfg(not(feature = "tract-backend"))]
+            {
+                bail!("rustbx was built without the `tract-backend` feature; rebuild with --features tract-backend");
+            }
+        }
+    }
 }
 
 fn main() -> Result<()> {
@@ -38,15 +156,16 @@ fn main() -> Result<()> {
         bail!("No input files specified. Usage: rustbx <input.nii.gz> [-o output_dir]");
     }
 
-    // Resolve model bytes
-    let external_model;
-    let model_bytes: &[u8] = if let Some(ref model_path) = args.model {
-        if !model_path.exists() {
-            bail!("Model file not found: {}", model_path.display());
+    // Resolve model bytes: embedded, a local file, or a downloaded/cached URL
+    let resolved_model;
+    let model_bytes: &[u8] = if let Some(ref model_arg) = args.model {
+        let location = model_source::ModelLocation::parse(model_arg);
+        resolved_model =
+            model_source::resolve_model_bytes(&location, args.model_sha256.as_deref())?;
+        if let model_source::ModelLocation::Fs(ref path) = location {
+            println!("Model: {} (external)", path.display());
         }
-        println!("Model: {} (external)", model_path.display());
-        external_model = std::fs::read(model_path)?;
-        &external_model
+        &resolved_model
     } else {
         println!("Model: embedded ({:.1} MB)", EMBEDDED_MODEL.len() as f64 / 1_048_576.0);
         EMBEDDED_MODEL
@@ -63,45 +182,234 @@ fn main() -> Result<()> {
         std::fs::create_dir_all(out_dir)?;
     }
 
-    // Build ONNX session once, reuse for all files
-    println!("Loading model...");
-    let mut session = inference::create_session(model_bytes)?;
-    println!("Model loaded.\n");
+    if let Some(iterations) = args.bench {
+        return run_bench_mode(&files, &args, model_bytes, iterations);
+    }
 
     println!("Found {} file(s) to process\n", files.len());
 
-    // Process each file
+    let errors = if args.jobs > 1 {
+        run_parallel(&files, &args, model_bytes)?
+    } else {
+        run_sequential(&files, &args, model_bytes)?
+    };
+
+    if !errors.is_empty() {
+        println!("\n{} of {} file(s) failed:", errors.len(), files.len());
+        for (path, err) in &errors {
+            println!("  {}: {:#}", path.display(), err);
+        }
+    }
+
+    println!("Done!");
+    Ok(())
+}
+
+/// Run `--bench` mode: load the model once, then benchmark each input in
+/// turn and print a min/mean/max-per-stage report (table or JSON).
+fn run_bench_mode(files: &[PathBuf], args: &Args, model_bytes: &[u8], iterations: usize) -> Result<()> {
+    // Always leave at least one measured run: clamp rather than error out,
+    // so e.g. `--bench 1` with the default `--bench-warmup 1` still works.
+    let warmup = args.bench_warmup.min(iterations.saturating_sub(1));
+    if warmup < args.bench_warmup {
+        println!(
+            "Warning: clamping --bench-warmup from {} to {warmup} to leave at least one measured run",
+            args.bench_warmup
+        );
+    }
+
+    println!("Loading model ({:?} backend, {:?} device)...", args.backend, args.device);
+    let mut backend = load_backend(args.backend, model_bytes, args.device, args.fp16)?;
+    println!("Model loaded.\n");
+
+    let mut reports = Vec::new();
+    for input in files {
+        println!(
+            "Benchmarking {} ({} runs, {} warm-up)...",
+            input.display(),
+            iterations,
+            warmup
+        );
+        reports.push(bench::bench_file(input, backend.as_mut(), iterations, warmup)?);
+    }
+
+    if args.bench_json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+
+    for report in &reports {
+        println!("\n{}", report.file.display());
+        println!(
+            "  {:<28} {:>10} {:>10} {:>10}",
+            "stage", "min (ms)", "mean (ms)", "max (ms)"
+        );
+        for stage in &report.stages {
+            println!(
+                "  {:<28} {:>10.2} {:>10.2} {:>10.2}",
+                stage.stage, stage.min_ms, stage.mean_ms, stage.max_ms
+            );
+        }
+        println!(
+            "  {} voxels, {:.2} Mvoxels/s ({} measured run(s))",
+            report.voxels,
+            report.voxels_per_sec / 1_000_000.0,
+            report.iterations - report.warmup
+        );
+    }
+
+    Ok(())
+}
+
+/// Process files one at a time on the current thread, printing progress as plain text.
+fn run_sequential(
+    files: &[PathBuf],
+    args: &Args,
+    model_bytes: &[u8],
+) -> Result<Vec<(PathBuf, anyhow::Error)>> {
+    println!("Loading model ({:?} backend, {:?} device)...", args.backend, args.device);
+    let mut backend = load_backend(args.backend, model_bytes, args.device, args.fp16)?;
+    println!("Model loaded.\n");
+
+    let mut errors = Vec::new();
     for (i, input) in files.iter().enumerate() {
         println!("[{}/{}] {}", i + 1, files.len(), input.display());
-        if let Err(e) = process_file(input, args.output.as_deref(), &mut session) {
+        let mut timing = bench::TimingCollector::new();
+        if let Err(e) = process_file(
+            input,
+            args.output.as_deref(),
+            backend.as_mut(),
+            &PlainReporter,
+            &mut timing,
+        ) {
             eprintln!("  ERROR: {:#}", e);
+            errors.push((input.clone(), e));
         }
         println!();
     }
 
-    println!("Done!");
-    Ok(())
+    Ok(errors)
+}
+
+/// Process files across `args.jobs` worker threads, each with its own inference
+/// backend (backends aren't cheaply shareable across threads). Progress is
+/// rendered with `indicatif` instead of interleaved `println!`s; per-file
+/// errors are collected and returned instead of printed immediately, so the
+/// final report stays in deterministic, input order.
+fn run_parallel(
+    files: &[PathBuf],
+    args: &Args,
+    model_bytes: &[u8],
+) -> Result<Vec<(PathBuf, anyhow::Error)>> {
+    println!(
+        "Loading model ({:?} backend, {:?} device) across {} worker(s)...\n",
+        args.backend, args.device, args.jobs
+    );
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs)
+        .build()
+        .context("Failed to build worker thread pool")?;
+
+    let multi = MultiProgress::new();
+    let total_bar = multi.add(ProgressBar::new(files.len() as u64));
+    total_bar.set_style(
+        ProgressStyle::with_template("{prefix:>10} [{bar:30}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    total_bar.set_prefix("Total");
+
+    let file_bar_style = ProgressStyle::with_template("{prefix:>10} {spinner} {msg} ({elapsed})")
+        .unwrap()
+        .tick_chars("/|\\- ");
+
+    thread_local! {
+        static BACKEND: RefCell<Option<Box<dyn InferenceBackend>>> = const { RefCell::new(None) };
+    }
+
+    let results: Vec<(PathBuf, Result<()>)> = pool.install(|| {
+        files
+            .par_iter()
+            .map(|input| {
+                let file_bar = multi.add(ProgressBar::new_spinner());
+                file_bar.set_style(file_bar_style.clone());
+                file_bar.set_prefix(
+                    input
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                );
+                file_bar.enable_steady_tick(std::time::Duration::from_millis(100));
+
+                let result = BACKEND.with(|cell| -> Result<()> {
+                    let mut cell = cell.borrow_mut();
+                    if cell.is_none() {
+                        *cell = Some(load_backend(
+                            args.backend,
+                            model_bytes,
+                            args.device,
+                            args.fp16,
+                        )?);
+                    }
+                    let backend = cell.as_mut().expect("backend was just initialized");
+                    let reporter = BarReporter { bar: &file_bar };
+                    let mut timing = bench::TimingCollector::new();
+                    process_file(
+                        input,
+                        args.output.as_deref(),
+                        backend.as_mut(),
+                        &reporter,
+                        &mut timing,
+                    )
+                });
+
+                match &result {
+                    Ok(()) => file_bar.finish_with_message("done"),
+                    Err(e) => file_bar.finish_with_message(format!("FAILED: {e:#}")),
+                }
+                total_bar.inc(1);
+
+                (input.clone(), result)
+            })
+            .collect()
+    });
+
+    total_bar.finish_with_message("done");
+
+    Ok(results
+        .into_iter()
+        .filter_map(|(path, result)| result.err().map(|e| (path, e)))
+        .collect())
 }
 
 /// Process a single NIfTI file through the brain-extraction pipeline.
-fn process_file(input: &Path, output_dir: Option<&Path>, session: &mut ort::session::Session) -> Result<()> {
-    let start = std::time::Instant::now();
+fn process_file(
+    input: &Path,
+    output_dir: Option<&Path>,
+    backend: &mut dyn InferenceBackend,
+    reporter: &dyn StageReporter,
+    timing: &mut bench::TimingCollector,
+) -> Result<()> {
+    let start = Instant::now();
     let (tbx_path, tbxmask_path) = output_paths(input, output_dir);
 
     // 1. Read NIfTI
-    let (header, data) = nifti_io::read_nifti(input)?;
+    let (header, data) = timing.record("nifti_read", || nifti_io::read_nifti(input))?;
 
     // 2. Run brain extraction
-    let (mask, brain) = bx::run_bx(&data, session)?;
+    let (mask, brain) = bx::run_bx(&data, backend, reporter, timing)?;
 
     // 3. Write outputs
-    nifti_io::write_nifti(&tbx_path, &header, &brain)?;
-    println!("  -> {}", tbx_path.display());
-
-    nifti_io::write_nifti_u8(&tbxmask_path, &header, &mask)?;
-    println!("  -> {}", tbxmask_path.display());
+    timing.record("nifti_write", || -> Result<()> {
+        nifti_io::write_nifti(&tbx_path, &header, &brain)?;
+        nifti_io::write_nifti_u8(&tbxmask_path, &header, &mask)?;
+        Ok(())
+    })?;
+    reporter.stage(&format!("-> {}", tbx_path.display()));
+    reporter.stage(&format!("-> {}", tbxmask_path.display()));
 
-    println!("  Done in {}s", start.elapsed().as_secs());
+    reporter.stage(&format!("Done in {:.1}s", start.elapsed().as_secs_f64()));
     Ok(())
 }
 
@@ -0,0 +1,189 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Where to load the ONNX model bytes from.
+#[derive(Debug, Clone)]
+pub enum ModelLocation {
+    /// A file already present on disk.
+    Fs(PathBuf),
+    /// A model to download (and cache) from an HTTP(S) URL.
+    Http(Url),
+}
+
+impl ModelLocation {
+    /// Parse a `--model` argument: an `http(s)://` URL, or a plain
+    /// filesystem path.
+    pub fn parse(raw: &str) -> Self {
+        match Url::parse(raw) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+                ModelLocation::Http(url)
+            }
+            _ => ModelLocation::Fs(PathBuf::from(raw)),
+        }
+    }
+}
+
+/// Resolve a [`ModelLocation`] to in-memory model bytes.
+///
+/// `Http` locations are downloaded once and cached under
+/// `<platform cache dir>/rustbx/<sha256-of-url>.onnx` (`~/.cache` on
+/// Linux), alongside a `.meta` sidecar
+/// recording the downloaded byte length and SHA-256; later runs verify
+/// the cached file against that sidecar before reusing it, and fall back
+/// to re-downloading on any mismatch. `expected_sha256`, if given, must
+/// additionally match.
+pub fn resolve_model_bytes(
+    location: &ModelLocation,
+    expected_sha256: Option<&str>,
+) -> Result<Vec<u8>> {
+    match location {
+        ModelLocation::Fs(path) => {
+            if !path.exists() {
+                bail!("Model file not found: {}", path.display());
+            }
+            std::fs::read(path)
+                .with_context(|| format!("Failed to read model file: {}", path.display()))
+        }
+        ModelLocation::Http(url) => fetch_cached(url, expected_sha256),
+    }
+}
+
+fn cache_path_for(url: &Url) -> Result<PathBuf> {
+    let cache_dir = dirs::cache_dir()
+        .context("Could not determine a cache directory for this platform")?
+        .join("rustbx");
+    std::fs::create_dir_all(&cache_dir)
+        .with_context(|| format!("Failed to create cache directory: {}", cache_dir.display()))?;
+
+    Ok(cache_dir.join(format!("{}.onnx", sha256_hex(url.as_str().as_bytes()))))
+}
+
+/// Sidecar path recording the size/digest a cached model was downloaded with.
+fn meta_path_for(cache_path: &Path) -> PathBuf {
+    let mut name = cache_path.as_os_str().to_owned();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+fn fetch_cached(url: &Url, expected_sha256: Option<&str>) -> Result<Vec<u8>> {
+    let cache_path = cache_path_for(url)?;
+    let meta_path = meta_path_for(&cache_path);
+
+    if cache_path.exists() {
+        match read_verified_cache(&cache_path, &meta_path, expected_sha256) {
+            Some(cached) => {
+                println!("Model: {} (cached at {})", url, cache_path.display());
+                return Ok(cached);
+            }
+            None => println!("  Cached model failed verification, re-downloading..."),
+        }
+    }
+
+    println!("Downloading model from {url} ...");
+    let (bytes, content_length) = download(url)?;
+
+    if bytes.is_empty() {
+        bail!("Downloaded model from {url} is empty");
+    }
+    // `Content-Length` reflects the size of the encoded body on the wire,
+    // but ureq transparently decodes `Content-Encoding` (e.g. gzip) before
+    // we ever see it — so for an encoded response `bytes.len()` is the
+    // decoded size and can never match. Only trust the comparison for an
+    // identity-encoded response.
+    if let Some(expected_len) = content_length {
+        if bytes.len() as u64 != expected_len {
+            bail!(
+                "Downloaded model from {url} is truncated: got {} bytes, expected {}",
+                bytes.len(),
+                expected_len
+            );
+        }
+    }
+    let digest = sha256_hex(&bytes);
+    if let Some(expected) = expected_sha256 {
+        if !digest.eq_ignore_ascii_case(expected) {
+            bail!("Downloaded model from {url} failed SHA-256 verification");
+        }
+    }
+
+    std::fs::write(&cache_path, &bytes)
+        .with_context(|| format!("Failed to write model cache: {}", cache_path.display()))?;
+    std::fs::write(&meta_path, format!("{}\n{digest}\n", bytes.len()))
+        .with_context(|| format!("Failed to write model cache metadata: {}", meta_path.display()))?;
+    println!("Model cached at {}", cache_path.display());
+
+    Ok(bytes)
+}
+
+/// Read a cached model and validate it against its `.meta` sidecar (byte
+/// length and SHA-256 recorded at download time), plus `expected_sha256`
+/// if the caller supplied one. Returns `None` on any mismatch or missing
+/// metadata, which forces a re-download rather than risking a corrupt or
+/// truncated cache entry being reused silently.
+fn read_verified_cache(
+    cache_path: &Path,
+    meta_path: &Path,
+    expected_sha256: Option<&str>,
+) -> Option<Vec<u8>> {
+    let meta = std::fs::read_to_string(meta_path).ok()?;
+    let mut lines = meta.lines();
+    let recorded_len: u64 = lines.next()?.parse().ok()?;
+    let recorded_sha256 = lines.next()?;
+
+    let cached = std::fs::read(cache_path).ok()?;
+    if cached.is_empty() || cached.len() as u64 != recorded_len {
+        return None;
+    }
+    if sha256_hex(&cached) != recorded_sha256 {
+        return None;
+    }
+    if let Some(expected) = expected_sha256 {
+        if !recorded_sha256.eq_ignore_ascii_case(expected) {
+            return None;
+        }
+    }
+
+    Some(cached)
+}
+
+/// Download `url`'s body, along with its `Content-Length` if the server
+/// sent one *and* that length can actually be compared against the bytes
+/// we read back.
+///
+/// ureq transparently decodes a `Content-Encoding` response (e.g. gzip)
+/// before handing us the body, but `Content-Length` still reports the
+/// size of the encoded bytes on the wire. For such a response the header
+/// and the decoded byte count are never comparable, so we don't return a
+/// length to check against in that case.
+fn download(url: &Url) -> Result<(Vec<u8>, Option<u64>)> {
+    let response = ureq::get(url.as_str())
+        .call()
+        .with_context(|| format!("Failed to download model from {url}"))?;
+
+    let is_identity_encoded = match response.header("Content-Encoding") {
+        Some(encoding) => encoding.eq_ignore_ascii_case("identity"),
+        None => true,
+    };
+    let content_length = if is_identity_encoded {
+        response
+            .header("Content-Length")
+            .and_then(|v| v.parse::<u64>().ok())
+    } else {
+        None
+    };
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .context("Failed to read downloaded model body")?;
+
+    Ok((bytes, content_length))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,30 @@
+use indicatif::ProgressBar;
+
+/// Where per-stage status messages for a single file's pipeline go.
+///
+/// In sequential mode this is a line on stdout, same as before. In
+/// parallel (`--jobs`) mode it instead updates that file's own `indicatif`
+/// bar so concurrent workers don't interleave their output.
+pub trait StageReporter {
+    fn stage(&self, message: &str);
+}
+
+/// Prints each stage as its own indented line.
+pub struct PlainReporter;
+
+impl StageReporter for PlainReporter {
+    fn stage(&self, message: &str) {
+        println!("  {message}");
+    }
+}
+
+/// Updates an `indicatif` progress bar's message instead of printing.
+pub struct BarReporter<'a> {
+    pub bar: &'a ProgressBar,
+}
+
+impl StageReporter for BarReporter<'_> {
+    fn stage(&self, message: &str) {
+        self.bar.set_message(message.to_string());
+    }
+}